@@ -0,0 +1,12 @@
+use sha3::{Digest, Keccak256};
+
+/// Computes the Keccak-256 digest used throughout the rollup for addresses,
+/// trie nodes and transaction signing hashes.
+pub fn keccak256(data: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak256::new();
+    hasher.update(data);
+    let digest = hasher.finalize();
+    let mut out = [0u8; 32];
+    out.copy_from_slice(&digest);
+    out
+}
@@ -0,0 +1,451 @@
+//! A minimal Merkle Patricia Trie used as the rollup's state root, modeled
+//! after the `state/mod.rs` tries in cita-state / OpenEthereum: accounts are
+//! keyed by `keccak256(address)` and the leaf value is an RLP-ish encoding of
+//! `(nonce, balance)`. Every node's identity is `keccak256(encode(node))`, so
+//! a verifier can recompute hashes along a path without holding the rest of
+//! the state.
+
+use std::collections::HashMap;
+
+use crate::hash::keccak256;
+use crate::u256::U256;
+use crate::{Account, Address};
+
+/// One of the three classic MPT node kinds.
+#[derive(Clone, Debug, PartialEq)]
+enum Node {
+    /// Remaining nibble path (hex-prefix encoded on the wire) plus the leaf value.
+    Leaf { path: Vec<u8>, value: Vec<u8> },
+    /// Shared nibble prefix plus the hash of the single child it points to.
+    Extension { path: Vec<u8>, child: [u8; 32] },
+    /// 16 child slots keyed by nibble, plus an optional value for a key that
+    /// terminates exactly at this node. Boxed so a `Leaf`/`Extension` (the
+    /// overwhelmingly common case) doesn't pay for the 16-slot array.
+    Branch { children: Box<[Option<[u8; 32]>; 16]>, value: Option<Vec<u8>> },
+}
+
+/// Keccak-256 of the empty-trie encoding, returned as the root of a trie with
+/// no accounts in it.
+fn empty_root() -> [u8; 32] {
+    keccak256(&[])
+}
+
+/// Merkle Patricia Trie over the rollup's account set.
+#[derive(Clone)]
+pub struct StateTrie {
+    nodes: HashMap<[u8; 32], Node>,
+    root: Option<[u8; 32]>,
+}
+
+impl StateTrie {
+    pub fn new() -> Self {
+        StateTrie { nodes: HashMap::new(), root: None }
+    }
+
+    /// Rebuilds a trie from scratch from an account set, inserting in
+    /// address order so the result is deterministic.
+    pub fn from_accounts<'a>(accounts: impl IntoIterator<Item = (&'a Address, &'a Account)>) -> Self {
+        let mut trie = StateTrie::new();
+        let mut sorted: Vec<_> = accounts.into_iter().collect();
+        sorted.sort_by(|a, b| a.0.cmp(b.0));
+        for (address, account) in sorted {
+            trie.insert(address, account);
+        }
+        trie
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        self.root.unwrap_or_else(empty_root)
+    }
+
+    /// Reconstructs a sparse, verifier-side trie containing only the nodes
+    /// carried by the given inclusion proofs, rooted at `root`. Inserting
+    /// into (or reading from) the addresses those proofs witnessed works
+    /// exactly as it would on the full trie -- every node the walk touches
+    /// is guaranteed to be present -- without ever materializing the rest
+    /// of the state.
+    pub fn from_witness<'a>(root: [u8; 32], proofs: impl IntoIterator<Item = &'a Vec<Vec<u8>>>) -> StateTrie {
+        let mut nodes = HashMap::new();
+        for proof in proofs {
+            for encoded in proof {
+                if let Some(node) = decode_node(encoded) {
+                    nodes.insert(keccak256(encoded), node);
+                }
+            }
+        }
+        StateTrie { nodes, root: Some(root) }
+    }
+
+    pub fn insert(&mut self, address: &Address, account: &Account) {
+        let path = address_nibbles(address);
+        let value = encode_account(account);
+        self.root = Some(self.insert_at(self.root, &path, value));
+    }
+
+    pub fn get(&self, address: &Address) -> Option<Account> {
+        let path = address_nibbles(address);
+        let mut node_hash = self.root?;
+        let mut remaining = &path[..];
+        loop {
+            match self.nodes.get(&node_hash)? {
+                Node::Leaf { path: leaf_path, value } => {
+                    return if leaf_path.as_slice() == remaining { decode_account(value) } else { None };
+                }
+                Node::Extension { path: ext_path, child } => {
+                    if !remaining.starts_with(ext_path.as_slice()) {
+                        return None;
+                    }
+                    remaining = &remaining[ext_path.len()..];
+                    node_hash = *child;
+                }
+                Node::Branch { children, value } => {
+                    if remaining.is_empty() {
+                        return value.as_ref().and_then(|v| decode_account(v));
+                    }
+                    let child = children[remaining[0] as usize]?;
+                    remaining = &remaining[1..];
+                    node_hash = child;
+                }
+            }
+        }
+    }
+
+    /// Returns the ordered list of encoded nodes from the root down to (and
+    /// including) the leaf holding `address`, suitable for `verify_proof`.
+    pub fn prove(&self, address: &Address) -> Option<Vec<Vec<u8>>> {
+        let path = address_nibbles(address);
+        let mut proof = Vec::new();
+        let mut node_hash = self.root?;
+        let mut remaining = &path[..];
+        loop {
+            let node = self.nodes.get(&node_hash)?;
+            proof.push(encode_node(node));
+            match node {
+                Node::Leaf { path: leaf_path, .. } => {
+                    return if leaf_path.as_slice() == remaining { Some(proof) } else { None };
+                }
+                Node::Extension { path: ext_path, child } => {
+                    if !remaining.starts_with(ext_path.as_slice()) {
+                        return None;
+                    }
+                    remaining = &remaining[ext_path.len()..];
+                    node_hash = *child;
+                }
+                Node::Branch { children, value } => {
+                    if remaining.is_empty() {
+                        return if value.is_some() { Some(proof) } else { None };
+                    }
+                    node_hash = (*children.get(remaining[0] as usize)?)?;
+                    remaining = &remaining[1..];
+                }
+            }
+        }
+    }
+
+    fn put(&mut self, node: Node) -> [u8; 32] {
+        let encoded = encode_node(&node);
+        let hash = keccak256(&encoded);
+        self.nodes.insert(hash, node);
+        hash
+    }
+
+    fn node(&self, hash: [u8; 32]) -> Node {
+        self.nodes.get(&hash).cloned().expect("dangling trie node hash")
+    }
+
+    fn insert_at(&mut self, node_hash: Option<[u8; 32]>, path: &[u8], value: Vec<u8>) -> [u8; 32] {
+        let Some(hash) = node_hash else {
+            return self.put(Node::Leaf { path: path.to_vec(), value });
+        };
+
+        match self.node(hash) {
+            Node::Leaf { path: existing_path, value: existing_value } => {
+                if existing_path == path {
+                    return self.put(Node::Leaf { path: path.to_vec(), value });
+                }
+                let common = common_prefix_len(&existing_path, path);
+                let branch = self.merge_diverging(
+                    &existing_path[common..],
+                    existing_value,
+                    &path[common..],
+                    value,
+                );
+                self.wrap_in_extension(&existing_path[..common], branch)
+            }
+            Node::Extension { path: ext_path, child } => {
+                let common = common_prefix_len(&ext_path, path);
+                if common == ext_path.len() {
+                    let new_child = self.insert_at(Some(child), &path[common..], value);
+                    return self.put(Node::Extension { path: ext_path, child: new_child });
+                }
+
+                // Split the extension at the divergence point: one branch
+                // slot continues through its remaining nibbles to the
+                // original `child`, the other holds the new leaf.
+                let ext_remainder = &ext_path[common..];
+                let new_remainder = &path[common..];
+                let mut children: Box<[Option<[u8; 32]>; 16]> = Default::default();
+                let mut branch_value = None;
+
+                let ext_idx = ext_remainder[0] as usize;
+                children[ext_idx] = Some(if ext_remainder.len() == 1 {
+                    child
+                } else {
+                    self.put(Node::Extension { path: ext_remainder[1..].to_vec(), child })
+                });
+
+                if new_remainder.is_empty() {
+                    branch_value = Some(value);
+                } else {
+                    let new_idx = new_remainder[0] as usize;
+                    let leaf = self.put(Node::Leaf { path: new_remainder[1..].to_vec(), value });
+                    children[new_idx] = Some(leaf);
+                }
+
+                let branch = self.put(Node::Branch { children, value: branch_value });
+                self.wrap_in_extension(&path[..common], branch)
+            }
+            Node::Branch { mut children, value: branch_value } => {
+                if path.is_empty() {
+                    return self.put(Node::Branch { children, value: Some(value) });
+                }
+                let idx = path[0] as usize;
+                let new_child = self.insert_at(children[idx], &path[1..], value);
+                children[idx] = Some(new_child);
+                self.put(Node::Branch { children, value: branch_value })
+            }
+        }
+    }
+
+    /// Builds a branch node joining two already-diverged (common prefix
+    /// stripped) paths, each terminating in a leaf holding `value_a`/`value_b`.
+    fn merge_diverging(&mut self, path_a: &[u8], value_a: Vec<u8>, path_b: &[u8], value_b: Vec<u8>) -> [u8; 32] {
+        let mut children: Box<[Option<[u8; 32]>; 16]> = Default::default();
+        let mut branch_value = None;
+
+        if path_a.is_empty() {
+            branch_value = Some(value_a);
+        } else {
+            let idx = path_a[0] as usize;
+            let leaf = self.put(Node::Leaf { path: path_a[1..].to_vec(), value: value_a });
+            children[idx] = Some(leaf);
+        }
+        if path_b.is_empty() {
+            branch_value = Some(value_b);
+        } else {
+            let idx = path_b[0] as usize;
+            let leaf = self.put(Node::Leaf { path: path_b[1..].to_vec(), value: value_b });
+            children[idx] = Some(leaf);
+        }
+        self.put(Node::Branch { children, value: branch_value })
+    }
+
+    fn wrap_in_extension(&mut self, prefix: &[u8], child: [u8; 32]) -> [u8; 32] {
+        if prefix.is_empty() {
+            child
+        } else {
+            self.put(Node::Extension { path: prefix.to_vec(), child })
+        }
+    }
+}
+
+/// Verifies that `account` is the value stored for `address` under `root`,
+/// given the ordered proof nodes returned by `StateTrie::prove`. Runs in
+/// O(proof size): each node is re-hashed and the nibble path is followed
+/// without touching the rest of the state.
+pub fn verify_proof(root: [u8; 32], address: &Address, account: &Account, proof: &[Vec<u8>]) -> bool {
+    let path = address_nibbles(address);
+    let mut remaining = &path[..];
+    let mut expected_hash = root;
+
+    for (i, encoded) in proof.iter().enumerate() {
+        if keccak256(encoded) != expected_hash {
+            return false;
+        }
+        let Some(node) = decode_node(encoded) else { return false };
+        let is_last = i == proof.len() - 1;
+        match node {
+            Node::Leaf { path: leaf_path, value } => {
+                if !is_last || leaf_path != remaining {
+                    return false;
+                }
+                return decode_account(&value).as_ref() == Some(account);
+            }
+            Node::Extension { path: ext_path, child } => {
+                if !remaining.starts_with(ext_path.as_slice()) {
+                    return false;
+                }
+                remaining = &remaining[ext_path.len()..];
+                expected_hash = child;
+            }
+            Node::Branch { children, value } => {
+                if remaining.is_empty() {
+                    return is_last && value.as_ref().and_then(|v| decode_account(v)).as_ref() == Some(account);
+                }
+                let Some(child) = children[remaining[0] as usize] else { return false };
+                remaining = &remaining[1..];
+                expected_hash = child;
+            }
+        }
+    }
+    false
+}
+
+fn address_nibbles(address: &Address) -> Vec<u8> {
+    bytes_to_nibbles(&keccak256(address))
+}
+
+fn common_prefix_len(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b.iter()).take_while(|(x, y)| x == y).count()
+}
+
+fn bytes_to_nibbles(bytes: &[u8]) -> Vec<u8> {
+    bytes.iter().flat_map(|b| [b >> 4, b & 0x0f]).collect()
+}
+
+fn nibbles_to_bytes(nibbles: &[u8]) -> Vec<u8> {
+    nibbles.chunks(2).map(|pair| (pair[0] << 4) | pair.get(1).copied().unwrap_or(0)).collect()
+}
+
+/// Hex-prefix encodes a nibble path so the wire form is unambiguous about
+/// whether it terminates in a leaf and whether its length is odd.
+fn hex_prefix_encode(path: &[u8], is_leaf: bool) -> Vec<u8> {
+    let odd = path.len() % 2 == 1;
+    let flag = (if is_leaf { 2 } else { 0 }) | (if odd { 1 } else { 0 });
+    let mut nibbles = Vec::with_capacity(path.len() + 2);
+    nibbles.push(flag);
+    if !odd {
+        nibbles.push(0);
+    }
+    nibbles.extend_from_slice(path);
+    nibbles_to_bytes(&nibbles)
+}
+
+fn hex_prefix_decode(bytes: &[u8]) -> (Vec<u8>, bool) {
+    let nibbles = bytes_to_nibbles(bytes);
+    let is_leaf = nibbles[0] & 2 != 0;
+    let odd = nibbles[0] & 1 != 0;
+    let path = if odd { nibbles[1..].to_vec() } else { nibbles[2..].to_vec() };
+    (path, is_leaf)
+}
+
+/// Length-prefixed byte string, the building block of the trie's "RLP-ish"
+/// node encoding (a simplified stand-in for real RLP).
+fn encode_item(bytes: &[u8]) -> Vec<u8> {
+    let mut out = (bytes.len() as u32).to_be_bytes().to_vec();
+    out.extend_from_slice(bytes);
+    out
+}
+
+fn decode_item(bytes: &[u8]) -> Option<(&[u8], &[u8])> {
+    if bytes.len() < 4 {
+        return None;
+    }
+    let len = u32::from_be_bytes(bytes[0..4].try_into().unwrap()) as usize;
+    let rest = &bytes[4..];
+    if rest.len() < len {
+        return None;
+    }
+    Some((&rest[..len], &rest[len..]))
+}
+
+fn encode_account(account: &Account) -> Vec<u8> {
+    let mut out = encode_item(&account.nonce.to_be_bytes());
+    out.extend(encode_item(&account.balance.to_bytes()));
+    out
+}
+
+fn decode_account(bytes: &[u8]) -> Option<Account> {
+    let (nonce_bytes, rest) = decode_item(bytes)?;
+    let (balance_bytes, _) = decode_item(rest)?;
+    Some(Account {
+        nonce: u64::from_be_bytes(nonce_bytes.try_into().ok()?),
+        balance: U256::from_bytes(balance_bytes.try_into().ok()?),
+    })
+}
+
+fn encode_node(node: &Node) -> Vec<u8> {
+    match node {
+        Node::Leaf { path, value } => {
+            let mut out = vec![0u8];
+            out.extend(encode_item(&hex_prefix_encode(path, true)));
+            out.extend(encode_item(value));
+            out
+        }
+        Node::Extension { path, child } => {
+            let mut out = vec![1u8];
+            out.extend(encode_item(&hex_prefix_encode(path, false)));
+            out.extend(encode_item(child));
+            out
+        }
+        Node::Branch { children, value } => {
+            let mut out = vec![2u8];
+            for child in children.iter() {
+                match child {
+                    Some(hash) => {
+                        out.push(1);
+                        out.extend_from_slice(hash);
+                    }
+                    None => out.push(0),
+                }
+            }
+            match value {
+                Some(v) => {
+                    out.push(1);
+                    out.extend(encode_item(v));
+                }
+                None => out.push(0),
+            }
+            out
+        }
+    }
+}
+
+fn decode_node(bytes: &[u8]) -> Option<Node> {
+    let (tag, rest) = bytes.split_first()?;
+    match tag {
+        0 => {
+            let (hp, rest) = decode_item(rest)?;
+            let (value, _) = decode_item(rest)?;
+            let (path, is_leaf) = hex_prefix_decode(hp);
+            if !is_leaf {
+                return None;
+            }
+            Some(Node::Leaf { path, value: value.to_vec() })
+        }
+        1 => {
+            let (hp, rest) = decode_item(rest)?;
+            let (child, _) = decode_item(rest)?;
+            let (path, is_leaf) = hex_prefix_decode(hp);
+            if is_leaf {
+                return None;
+            }
+            Some(Node::Extension { path, child: child.try_into().ok()? })
+        }
+        2 => {
+            let mut children: Box<[Option<[u8; 32]>; 16]> = Default::default();
+            let mut cursor = rest;
+            for slot in children.iter_mut() {
+                let (flag, next) = cursor.split_first()?;
+                cursor = next;
+                if *flag == 1 {
+                    if cursor.len() < 32 {
+                        return None;
+                    }
+                    let (hash, next) = cursor.split_at(32);
+                    *slot = Some(hash.try_into().ok()?);
+                    cursor = next;
+                }
+            }
+            let (flag, next) = cursor.split_first()?;
+            let value = if *flag == 1 {
+                let (v, _) = decode_item(next)?;
+                Some(v.to_vec())
+            } else {
+                None
+            };
+            Some(Node::Branch { children, value })
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,120 @@
+//! Transaction signing and signer recovery, split the way OpenEthereum
+//! splits `UnverifiedTransaction` from a verified transaction: everything
+//! that arrives over the wire carries an unchecked signature, and only
+//! `UnverifiedTransaction::verify` turns it into something the rest of the
+//! rollup is allowed to touch state with.
+
+use k256::ecdsa::{RecoveryId, Signature, SigningKey, VerifyingKey};
+
+use crate::hash::keccak256;
+use crate::{Address, U256};
+
+/// The unsigned fields of a transfer; their RLP-ish encoding is what gets
+/// signed and later re-hashed to recover the signer.
+#[derive(Clone)]
+pub struct UnsignedTransaction {
+    pub nonce: u64,
+    pub gas_price: U256,
+    pub gas_limit: u64,
+    pub to: Option<Address>,
+    pub value: U256,
+    pub data: Vec<u8>,
+}
+
+impl UnsignedTransaction {
+    fn signing_hash(&self) -> [u8; 32] {
+        let mut encoded = Vec::new();
+        encoded.extend_from_slice(&self.nonce.to_be_bytes());
+        encoded.extend_from_slice(&self.gas_price.to_bytes());
+        encoded.extend_from_slice(&self.gas_limit.to_be_bytes());
+        if let Some(to) = self.to {
+            encoded.extend_from_slice(&to);
+        }
+        encoded.extend_from_slice(&self.value.to_bytes());
+        encoded.extend_from_slice(&self.data);
+        keccak256(&encoded)
+    }
+}
+
+/// A transaction exactly as received over the wire: unsigned fields plus a
+/// `(v, r, s)` signature over their hash. The sender is not yet trusted --
+/// call `verify` (or `recover`) before it touches any state.
+#[derive(Clone)]
+pub struct UnverifiedTransaction {
+    pub unsigned: UnsignedTransaction,
+    pub v: u8,
+    pub r: U256,
+    pub s: U256,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SigError {
+    InvalidRecoveryId,
+    InvalidSignature,
+    RecoveryFailed,
+}
+
+impl UnverifiedTransaction {
+    /// Hashes the unsigned fields with keccak256 and runs secp256k1 public
+    /// key recovery to derive the 20-byte sender from
+    /// `keccak256(pubkey)[12..]`.
+    pub fn recover(&self) -> Result<Address, SigError> {
+        let hash = self.unsigned.signing_hash();
+
+        let mut sig_bytes = [0u8; 64];
+        sig_bytes[..32].copy_from_slice(&self.r.to_bytes());
+        sig_bytes[32..].copy_from_slice(&self.s.to_bytes());
+        let signature = Signature::from_bytes((&sig_bytes).into()).map_err(|_| SigError::InvalidSignature)?;
+        let recovery_id = RecoveryId::from_byte(self.v).ok_or(SigError::InvalidRecoveryId)?;
+
+        let verifying_key = VerifyingKey::recover_from_prehash(&hash, &signature, recovery_id)
+            .map_err(|_| SigError::RecoveryFailed)?;
+
+        Ok(address_from_verifying_key(&verifying_key))
+    }
+
+    /// Verifies the signature and bundles the recovered signer with the
+    /// transaction, producing the only form `apply_transaction` is ever
+    /// handed. The `(v, r, s)` signature has done its job recovering
+    /// `from` and isn't carried any further.
+    pub fn verify(self) -> Result<VerifiedTransaction, SigError> {
+        let from = self.recover()?;
+        Ok(VerifiedTransaction { from, unsigned: self.unsigned })
+    }
+}
+
+/// A transaction whose signature has been checked, so its sender is trusted.
+#[derive(Clone)]
+pub struct VerifiedTransaction {
+    pub from: Address,
+    pub unsigned: UnsignedTransaction,
+}
+
+fn address_from_verifying_key(verifying_key: &VerifyingKey) -> Address {
+    let uncompressed = verifying_key.to_sec1_point(false);
+    // Drop the leading 0x04 tag; the address is over the raw (x, y) pair.
+    let hashed = keccak256(&uncompressed.as_bytes()[1..]);
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&hashed[12..]);
+    address
+}
+
+/// Signs `unsigned` with `signing_key`, producing the wire form a client
+/// would actually submit. Used by the demo in `main` in place of a wallet.
+pub fn sign(unsigned: UnsignedTransaction, signing_key: &SigningKey) -> UnverifiedTransaction {
+    let hash = unsigned.signing_hash();
+    let (signature, recovery_id) = signing_key.sign_prehash_recoverable(&hash);
+    UnverifiedTransaction {
+        unsigned,
+        v: recovery_id.to_byte(),
+        r: U256::from_bytes(signature.r().to_bytes().into()),
+        s: U256::from_bytes(signature.s().to_bytes().into()),
+    }
+}
+
+/// The address that would be recovered from a signature made by
+/// `signing_key`. Used by the demo to fund the account it's about to sign
+/// transactions from.
+pub fn address_from_signing_key(signing_key: &SigningKey) -> Address {
+    address_from_verifying_key(signing_key.verifying_key())
+}
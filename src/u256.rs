@@ -0,0 +1,94 @@
+//! A fixed-width 256-bit unsigned integer stored big-endian, the actual
+//! representation behind balances and gas fields. Unlike treating a
+//! `[u8; 32]` as 32 independent saturating bytes, every operation here
+//! propagates carry/borrow across the whole width and reports overflow
+//! instead of silently wrapping or clamping a single byte.
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct U256([u8; 32]);
+
+impl U256 {
+    pub const ZERO: U256 = U256([0; 32]);
+
+    pub fn from_u64(value: u64) -> U256 {
+        let mut bytes = [0u8; 32];
+        bytes[24..].copy_from_slice(&value.to_be_bytes());
+        U256(bytes)
+    }
+
+    pub fn from_bytes(bytes: [u8; 32]) -> U256 {
+        U256(bytes)
+    }
+
+    pub fn to_bytes(self) -> [u8; 32] {
+        self.0
+    }
+
+    pub fn checked_add(self, rhs: U256) -> Option<U256> {
+        let mut out = [0u8; 32];
+        let mut carry = 0u16;
+        for i in (0..32).rev() {
+            let sum = self.0[i] as u16 + rhs.0[i] as u16 + carry;
+            out[i] = sum as u8;
+            carry = sum >> 8;
+        }
+        if carry != 0 {
+            None
+        } else {
+            Some(U256(out))
+        }
+    }
+
+    pub fn checked_sub(self, rhs: U256) -> Option<U256> {
+        let mut out = [0u8; 32];
+        let mut borrow = 0i16;
+        for i in (0..32).rev() {
+            let diff = self.0[i] as i16 - rhs.0[i] as i16 - borrow;
+            if diff < 0 {
+                out[i] = (diff + 256) as u8;
+                borrow = 1;
+            } else {
+                out[i] = diff as u8;
+                borrow = 0;
+            }
+        }
+        if borrow != 0 {
+            None
+        } else {
+            Some(U256(out))
+        }
+    }
+
+    /// Schoolbook long multiplication over base-256 digits, `None` if the
+    /// true product doesn't fit back into 256 bits.
+    pub fn checked_mul(self, rhs: U256) -> Option<U256> {
+        let mut digits = [0u32; 64];
+        for i in 0..32 {
+            let a = self.0[31 - i] as u32;
+            if a == 0 {
+                continue;
+            }
+            for j in 0..32 {
+                let b = rhs.0[31 - j] as u32;
+                digits[i + j] += a * b;
+            }
+        }
+
+        let mut carried = [0u32; 64];
+        let mut carry = 0u64;
+        for (k, digit) in digits.iter().enumerate() {
+            let value = *digit as u64 + carry;
+            carried[k] = (value & 0xFF) as u32;
+            carry = value >> 8;
+        }
+        if carry != 0 || carried[32..].iter().any(|&d| d != 0) {
+            return None;
+        }
+
+        let mut out = [0u8; 32];
+        for (k, digit) in carried[..32].iter().enumerate() {
+            out[31 - k] = *digit as u8;
+        }
+        Some(U256(out))
+    }
+}